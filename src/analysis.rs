@@ -1,19 +1,74 @@
-use rustfft::{num_complex::Complex, FftPlanner};
+use realfft::{num_complex::Complex, RealFftPlanner, RealToComplex};
+use std::sync::Arc;
 
 pub const SAMPLE_SIZE: usize = 2048;
 pub const NUM_BANDS: usize = 64;
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// How raw per-bin magnitudes are mapped into band intensities.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ScalingMode {
+    /// Magnitude as-is, then the classic per-frame peak normalize.
+    #[default]
+    Linear,
+    /// Magnitude divided by `sqrt(SAMPLE_SIZE)`. Not peak normalized, so
+    /// bands are on an absolute scale and can exceed 1.0.
+    DivideByNSqrt,
+    /// `20 * log10(magnitude)` mapped from an absolute dB window into 0..1.
+    /// Unlike Linear this is not re-normalized per frame, so the
+    /// bars reflect absolute level rather than "loudest band = full height".
+    Log { floor_db: f32, ceiling_db: f32 },
+}
+
+/// Tunable parameters for [`Analyzer`].
+#[derive(Clone, Copy, Debug)]
+pub struct AnalyzerConfig {
+    /// The real device sample rate the incoming samples were captured at.
+    /// Must match the source audio or bands will be mapped to the wrong
+    /// frequencies.
+    pub sample_rate: u32,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub scaling: ScalingMode,
+    /// Extra tilt, in dB per octave, layered on top of A-weighting (positive
+    /// emphasizes treble, negative flattens it). `None` disables the whole
+    /// perceptual weighting stage and bands are left exactly as measured.
+    pub tilt_db_per_octave: Option<f32>,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: SAMPLE_RATE,
+            min_freq: 20.0,
+            max_freq: 16000.0,
+            scaling: ScalingMode::Linear,
+            tilt_db_per_octave: None,
+        }
+    }
+}
 
 pub struct Analyzer {
-    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    fft: Arc<dyn RealToComplex<f32>>,
     window: Vec<f32>,
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
     smoothed: [f32; NUM_BANDS],
+    config: AnalyzerConfig,
+    /// Per-band linear gain from A-weighting + tilt; all 1.0 when disabled.
+    gain_table: [f32; NUM_BANDS],
 }
 
 impl Analyzer {
-    pub fn new() -> Self {
-        let mut planner = FftPlanner::new();
+    pub fn new(config: AnalyzerConfig) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
         let fft = planner.plan_fft_forward(SAMPLE_SIZE);
 
+        let input = fft.make_input_vec();
+        let output = fft.make_output_vec();
+        let scratch = fft.make_scratch_vec();
+
         // Hanning window
         let window: Vec<f32> = (0..SAMPLE_SIZE)
             .map(|i| {
@@ -21,41 +76,79 @@ impl Analyzer {
             })
             .collect();
 
+        let gain_table = Self::build_gain_table(&config);
+
         Self {
             fft,
             window,
+            input,
+            output,
+            scratch,
             smoothed: [0.0; NUM_BANDS],
+            config,
+            gain_table,
         }
     }
 
+    /// Precompute the per-band perceptual gain table from each band's
+    /// geometric-mean center frequency, so `process` is just a multiply.
+    fn build_gain_table(config: &AnalyzerConfig) -> [f32; NUM_BANDS] {
+        let mut gains = [1.0f32; NUM_BANDS];
+        let Some(tilt_db_per_octave) = config.tilt_db_per_octave else {
+            return gains;
+        };
+
+        for (band, gain) in gains.iter_mut().enumerate() {
+            let (freq_start, freq_end) = Self::band_freq_bounds(config, band);
+            let center = (freq_start * freq_end).sqrt();
+            let tilt_db = tilt_db_per_octave * (center / 1000.0).log2();
+            let gain_db = Self::a_weighting_db(center) + tilt_db;
+            *gain = 10f32.powf(gain_db / 20.0);
+        }
+
+        gains
+    }
+
+    /// IEC 61672 A-weighting curve in dB, normalized to 0dB at 1kHz.
+    fn a_weighting_db(freq: f32) -> f32 {
+        let f2 = freq * freq;
+        let numerator = 12194.0f32.powi(2) * f2 * f2;
+        let denominator = (f2 + 20.6f32.powi(2))
+            * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt()
+            * (f2 + 12194.0f32.powi(2));
+        20.0 * (numerator / denominator).log10() + 2.00
+    }
+
     /// Process raw audio samples into frequency bands.
     pub fn process(&mut self, samples: &[f32]) -> [f32; NUM_BANDS] {
         if samples.len() < SAMPLE_SIZE {
             return self.smoothed;
         }
 
-        // Apply window and convert to complex
-        let mut buffer: Vec<Complex<f32>> = samples
-            .iter()
-            .take(SAMPLE_SIZE)
-            .zip(self.window.iter())
-            .map(|(s, w)| Complex::new(s * w, 0.0))
-            .collect();
+        // Window the real samples into the input buffer. realfft is free to
+        // clobber this buffer during the transform, so it must be fully
+        // overwritten every frame rather than reused in place.
+        for (i, w) in self.window.iter().enumerate() {
+            self.input[i] = samples[i] * w;
+        }
 
-        // Run FFT
-        self.fft.process(&mut buffer);
+        self.fft
+            .process_with_scratch(&mut self.input, &mut self.output, &mut self.scratch)
+            .expect("realfft process_with_scratch failed");
 
-        // Convert to magnitudes (only first half is useful)
-        let magnitudes: Vec<f32> = buffer
+        // Convert to magnitudes and apply the configured scaling. The real
+        // transform yields SAMPLE_SIZE/2 + 1 bins (it includes the Nyquist
+        // term the complex transform discarded).
+        let magnitudes: Vec<f32> = self
+            .output
             .iter()
-            .take(SAMPLE_SIZE / 2)
-            .map(|c| c.norm())
+            .map(|c| self.scale_magnitude(c.norm()))
             .collect();
 
         // Group into bands (logarithmic scaling)
         let mut bands = [0.0f32; NUM_BANDS];
         for band in 0..NUM_BANDS {
-            let (start, end) = Self::band_range(band, SAMPLE_SIZE / 2);
+            let (start, end) = self.band_range(band, magnitudes.len());
             if start < end && end <= magnitudes.len() {
                 let sum: f32 = magnitudes[start..end].iter().sum();
                 let count = (end - start) as f32;
@@ -63,11 +156,35 @@ impl Analyzer {
             }
         }
 
-        // Normalize
-        let max = bands.iter().cloned().fold(0.0f32, f32::max);
-        if max > 0.0 {
-            for band in &mut bands {
-                *band /= max;
+        // Perceptual weighting (A-weighting + tilt), if configured. A no-op
+        // multiply by 1.0 when disabled, so there's no branch in the hot path.
+        for (band, gain) in bands.iter_mut().zip(self.gain_table.iter()) {
+            *band *= gain;
+        }
+
+        match self.config.scaling {
+            // Linear stays relative to the current frame's peak.
+            ScalingMode::Linear => {
+                let max = bands.iter().cloned().fold(0.0f32, f32::max);
+                if max > 0.0 {
+                    for band in &mut bands {
+                        *band /= max;
+                    }
+                }
+            }
+            // Dividing by a constant and then peak-normalizing cancels the
+            // constant out exactly, which would make this mode produce
+            // identical output to Linear. Leave it as the absolute (if
+            // unbounded) scale the mode is supposed to give instead.
+            ScalingMode::DivideByNSqrt => {}
+            // Log is already mapped into an absolute 0..1 dB window, so
+            // re-normalizing it would throw that away. Perceptual weighting
+            // can still push it back out of range, so clamp rather than
+            // re-normalize.
+            ScalingMode::Log { .. } => {
+                for band in &mut bands {
+                    *band = band.clamp(0.0, 1.0);
+                }
             }
         }
 
@@ -83,20 +200,39 @@ impl Analyzer {
         self.smoothed
     }
 
-    /// Get frequency bin range for a band (logarithmic distribution).
-    fn band_range(band: usize, total_bins: usize) -> (usize, usize) {
-        let min_freq = 20.0f32;
-        let max_freq = 16000.0f32;
-        let sample_rate = 44100.0f32;
-
-        let freq_per_bin = sample_rate / (total_bins as f32 * 2.0);
+    fn scale_magnitude(&self, mag: f32) -> f32 {
+        match self.config.scaling {
+            ScalingMode::Linear => mag,
+            ScalingMode::DivideByNSqrt => mag / (SAMPLE_SIZE as f32).sqrt(),
+            ScalingMode::Log {
+                floor_db,
+                ceiling_db,
+            } => {
+                let db = 20.0 * mag.max(1e-6).log10();
+                ((db - floor_db) / (ceiling_db - floor_db)).clamp(0.0, 1.0)
+            }
+        }
+    }
 
-        let log_min = min_freq.ln();
-        let log_max = max_freq.ln();
+    /// Start/end frequency of a band under the configured log distribution.
+    fn band_freq_bounds(config: &AnalyzerConfig, band: usize) -> (f32, f32) {
+        let log_min = config.min_freq.ln();
+        let log_max = config.max_freq.ln();
         let log_range = log_max - log_min;
 
         let freq_start = (log_min + (band as f32 / NUM_BANDS as f32) * log_range).exp();
         let freq_end = (log_min + ((band + 1) as f32 / NUM_BANDS as f32) * log_range).exp();
+        (freq_start, freq_end)
+    }
+
+    /// Get frequency bin range for a band (logarithmic distribution).
+    ///
+    /// `total_bins` is SAMPLE_SIZE/2 + 1 for the real-input transform (it
+    /// includes the Nyquist bin), but the bin width itself is still
+    /// sample_rate / SAMPLE_SIZE regardless of how many bins are passed in.
+    fn band_range(&self, band: usize, total_bins: usize) -> (usize, usize) {
+        let (freq_start, freq_end) = Self::band_freq_bounds(&self.config, band);
+        let freq_per_bin = self.config.sample_rate as f32 / SAMPLE_SIZE as f32;
 
         let bin_start = (freq_start / freq_per_bin) as usize;
         let bin_end = (freq_end / freq_per_bin) as usize;
@@ -111,13 +247,13 @@ mod tests {
 
     #[test]
     fn test_analyzer_creation() {
-        let analyzer = Analyzer::new();
+        let analyzer = Analyzer::new(AnalyzerConfig::default());
         assert_eq!(analyzer.window.len(), SAMPLE_SIZE);
     }
 
     #[test]
     fn test_process_silence() {
-        let mut analyzer = Analyzer::new();
+        let mut analyzer = Analyzer::new(AnalyzerConfig::default());
         let silence = vec![0.0f32; SAMPLE_SIZE];
         let bands = analyzer.process(&silence);
         for band in bands.iter() {
@@ -127,7 +263,7 @@ mod tests {
 
     #[test]
     fn test_process_sine_wave() {
-        let mut analyzer = Analyzer::new();
+        let mut analyzer = Analyzer::new(AnalyzerConfig::default());
         // Generate 440Hz sine wave
         let samples: Vec<f32> = (0..SAMPLE_SIZE)
             .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin())
@@ -141,11 +277,119 @@ mod tests {
 
     #[test]
     fn test_band_range_covers_spectrum() {
-        let total_bins = SAMPLE_SIZE / 2;
-        let (start_first, _) = Analyzer::band_range(0, total_bins);
-        let (_, end_last) = Analyzer::band_range(NUM_BANDS - 1, total_bins);
+        let analyzer = Analyzer::new(AnalyzerConfig::default());
+        let total_bins = SAMPLE_SIZE / 2 + 1;
+        let (start_first, _) = analyzer.band_range(0, total_bins);
+        let (_, end_last) = analyzer.band_range(NUM_BANDS - 1, total_bins);
 
         assert!(start_first < 10, "first band should start near beginning");
         assert!(end_last > 100, "last band should extend into higher bins");
     }
+
+    #[test]
+    fn test_band_range_follows_configured_sample_rate() {
+        let config_44k = AnalyzerConfig {
+            sample_rate: 44100,
+            ..AnalyzerConfig::default()
+        };
+        let config_48k = AnalyzerConfig {
+            sample_rate: 48000,
+            ..AnalyzerConfig::default()
+        };
+        let analyzer_44k = Analyzer::new(config_44k);
+        let analyzer_48k = Analyzer::new(config_48k);
+
+        let total_bins = SAMPLE_SIZE / 2 + 1;
+        let (_, end_44k) = analyzer_44k.band_range(NUM_BANDS - 1, total_bins);
+        let (_, end_48k) = analyzer_48k.band_range(NUM_BANDS - 1, total_bins);
+
+        assert_ne!(
+            end_44k, end_48k,
+            "bin mapping should shift with the device sample rate"
+        );
+    }
+
+    #[test]
+    fn test_divide_by_n_sqrt_does_not_collapse_to_linear() {
+        let mut linear = Analyzer::new(AnalyzerConfig::default());
+        let mut divided = Analyzer::new(AnalyzerConfig {
+            scaling: ScalingMode::DivideByNSqrt,
+            ..AnalyzerConfig::default()
+        });
+
+        let samples: Vec<f32> = (0..SAMPLE_SIZE)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+
+        let linear_bands = linear.process(&samples);
+        let divided_bands = divided.process(&samples);
+
+        assert_ne!(
+            linear_bands, divided_bands,
+            "DivideByNSqrt should not be canceled out by peak normalization"
+        );
+    }
+
+    #[test]
+    fn test_log_scaling_stays_within_unit_range() {
+        let mut analyzer = Analyzer::new(AnalyzerConfig {
+            scaling: ScalingMode::Log {
+                floor_db: -60.0,
+                ceiling_db: 0.0,
+            },
+            ..AnalyzerConfig::default()
+        });
+
+        let samples: Vec<f32> = (0..SAMPLE_SIZE)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let bands = analyzer.process(&samples);
+
+        for band in bands.iter() {
+            assert!(*band >= 0.0 && *band <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_disabled_weighting_is_all_unity_gain() {
+        let analyzer = Analyzer::new(AnalyzerConfig::default());
+        assert!(analyzer.gain_table.iter().all(|&g| g == 1.0));
+    }
+
+    #[test]
+    fn test_tilt_boosts_treble_relative_to_bass() {
+        let config = AnalyzerConfig {
+            tilt_db_per_octave: Some(3.0),
+            ..AnalyzerConfig::default()
+        };
+        let analyzer = Analyzer::new(config);
+        assert!(
+            analyzer.gain_table[NUM_BANDS - 1] > analyzer.gain_table[0],
+            "positive tilt should favor the highest band over the lowest"
+        );
+    }
+
+    #[test]
+    fn test_log_scaling_with_tilt_stays_within_unit_range() {
+        // A positive tilt on top of A-weighting can push a treble band's
+        // gain well past 0dB; Log mode must clamp back into 0..1 rather than
+        // relying on peak normalization (which it deliberately skips).
+        let mut analyzer = Analyzer::new(AnalyzerConfig {
+            scaling: ScalingMode::Log {
+                floor_db: -60.0,
+                ceiling_db: 0.0,
+            },
+            tilt_db_per_octave: Some(3.0),
+            ..AnalyzerConfig::default()
+        });
+
+        let samples: Vec<f32> = (0..SAMPLE_SIZE)
+            .map(|i| (2.0 * std::f32::consts::PI * 12000.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let bands = analyzer.process(&samples);
+
+        for band in bands.iter() {
+            assert!(*band >= 0.0 && *band <= 1.0);
+        }
+    }
 }
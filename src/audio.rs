@@ -1,12 +1,32 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Instant;
+
 use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Sample;
-use std::sync::{Arc, Mutex};
+use ringbuf::{
+    traits::{Consumer, Producer, Split},
+    HeapCons, HeapProd, HeapRb,
+};
+use rodio::Source as _;
 
 use crate::analysis::SAMPLE_SIZE;
 
+/// Anything that can hand the analyzer its next window of mono samples.
+pub trait AudioSource {
+    fn get_samples(&mut self) -> Vec<f32>;
+
+    /// The sample rate this source's samples are at, so the analyzer can map
+    /// bands to the right frequencies.
+    fn sample_rate(&self) -> u32;
+}
+
 pub struct AudioCapture {
-    buffer: Arc<Mutex<Vec<f32>>>,
+    consumer: HeapCons<f32>,
+    window: Vec<f32>,
+    sample_rate: u32,
     _stream: cpal::Stream,
 }
 
@@ -17,21 +37,24 @@ impl AudioCapture {
         // Try to get loopback device (system audio)
         let device = Self::find_loopback_device(&host)?;
         let config = device.default_output_config()?;
+        let sample_rate = config.sample_rate().0;
 
-        let buffer: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::with_capacity(SAMPLE_SIZE * 2)));
-        let buffer_clone = buffer.clone();
+        let rb = HeapRb::<f32>::new(SAMPLE_SIZE * 4);
+        let (producer, consumer) = rb.split();
 
         let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => Self::build_stream::<f32>(&device, &config.into(), buffer_clone)?,
-            cpal::SampleFormat::I16 => Self::build_stream::<i16>(&device, &config.into(), buffer_clone)?,
-            cpal::SampleFormat::U16 => Self::build_stream::<u16>(&device, &config.into(), buffer_clone)?,
+            cpal::SampleFormat::F32 => Self::build_stream::<f32>(&device, &config.into(), producer)?,
+            cpal::SampleFormat::I16 => Self::build_stream::<i16>(&device, &config.into(), producer)?,
+            cpal::SampleFormat::U16 => Self::build_stream::<u16>(&device, &config.into(), producer)?,
             _ => return Err(anyhow!("Unsupported sample format")),
         };
 
         stream.play()?;
 
         Ok(Self {
-            buffer,
+            consumer,
+            window: Vec::with_capacity(SAMPLE_SIZE * 2),
+            sample_rate,
             _stream: stream,
         })
     }
@@ -56,7 +79,7 @@ impl AudioCapture {
     fn build_stream<T>(
         device: &cpal::Device,
         config: &cpal::StreamConfig,
-        buffer: Arc<Mutex<Vec<f32>>>,
+        mut producer: HeapProd<f32>,
     ) -> Result<cpal::Stream>
     where
         T: cpal::Sample + cpal::SizedSample,
@@ -67,18 +90,16 @@ impl AudioCapture {
         let stream = device.build_input_stream(
             config,
             move |data: &[T], _: &cpal::InputCallbackInfo| {
-                let mut buf = buffer.lock().unwrap();
-
-                // Convert to mono f32
+                // Convert to mono f32 and push into the ring buffer. This
+                // never blocks or allocates: a split producer handle only
+                // supports `try_push`, so once the buffer between here and
+                // `get_samples` fills up we drop the newest sample rather
+                // than overwriting the oldest (the consumer keeps draining
+                // every frame, so this only bites if analysis stalls).
                 for frame in data.chunks(channels) {
                     let sum: f32 = frame.iter().map(|s| f32::from_sample(*s)).sum();
                     let mono = sum / channels as f32;
-                    buf.push(mono);
-                }
-
-                // Keep buffer size manageable
-                if buf.len() > SAMPLE_SIZE * 4 {
-                    buf.drain(0..SAMPLE_SIZE * 2);
+                    let _ = producer.try_push(mono);
                 }
             },
             |err| eprintln!("Audio stream error: {}", err),
@@ -88,13 +109,103 @@ impl AudioCapture {
         Ok(stream)
     }
 
+}
+
+impl AudioSource for AudioCapture {
     /// Get latest samples for analysis.
-    pub fn get_samples(&self) -> Vec<f32> {
-        let buf = self.buffer.lock().unwrap();
-        if buf.len() >= SAMPLE_SIZE {
-            buf[buf.len() - SAMPLE_SIZE..].to_vec()
+    ///
+    /// Drains whatever the callback has pushed since the last call into a
+    /// trailing window and returns its most recent `SAMPLE_SIZE` samples, so
+    /// the realtime producer side never has to wait on a lock.
+    fn get_samples(&mut self) -> Vec<f32> {
+        while let Some(sample) = self.consumer.try_pop() {
+            self.window.push(sample);
+        }
+
+        if self.window.len() > SAMPLE_SIZE * 2 {
+            let excess = self.window.len() - SAMPLE_SIZE * 2;
+            self.window.drain(0..excess);
+        }
+
+        if self.window.len() >= SAMPLE_SIZE {
+            self.window[self.window.len() - SAMPLE_SIZE..].to_vec()
         } else {
-            buf.clone()
+            self.window.clone()
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Decodes an audio file up front and feeds the analyzer from the current
+/// playback position, so a specific track can be visualized (and heard)
+/// without needing loopback capture at all.
+pub struct FileSource {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    start: Instant,
+    cursor: usize,
+    _stream: rodio::OutputStream,
+    _sink: rodio::Sink,
+}
+
+impl FileSource {
+    pub fn new(path: &Path) -> Result<Self> {
+        // Decode once to build the mono sample buffer the analyzer reads
+        // from, at the file's native rate; the analyzer is configured with
+        // that rate rather than assuming a fixed one.
+        let decode_file = File::open(path)?;
+        let decoder = rodio::Decoder::new(BufReader::new(decode_file))?;
+        let channels = decoder.channels().max(1) as usize;
+        let sample_rate = decoder.sample_rate();
+
+        let samples: Vec<f32> = decoder
+            .collect::<Vec<i16>>()
+            .chunks(channels)
+            .map(|frame| {
+                let sum: f32 = frame.iter().map(|s| *s as f32 / i16::MAX as f32).sum();
+                sum / channels as f32
+            })
+            .collect();
+
+        // Decode again for actual playback, so what's on screen tracks what's
+        // audible.
+        let playback_file = File::open(path)?;
+        let playback_decoder = rodio::Decoder::new(BufReader::new(playback_file))?;
+        let (stream, stream_handle) = rodio::OutputStream::try_default()?;
+        let sink = rodio::Sink::try_new(&stream_handle)?;
+        sink.append(playback_decoder);
+
+        Ok(Self {
+            samples,
+            sample_rate,
+            start: Instant::now(),
+            cursor: 0,
+            _stream: stream,
+            _sink: sink,
+        })
+    }
+}
+
+impl AudioSource for FileSource {
+    fn get_samples(&mut self) -> Vec<f32> {
+        // Advance the cursor to wherever wall-clock time says playback is,
+        // rather than just walking forward one window per call, so the
+        // spectrum can't drift out of sync with what's audible.
+        let elapsed_samples = (self.start.elapsed().as_secs_f32() * self.sample_rate as f32) as usize;
+        self.cursor = elapsed_samples;
+
+        if self.cursor >= self.samples.len() {
+            return Vec::new();
         }
+
+        let end = (self.cursor + SAMPLE_SIZE).min(self.samples.len());
+        self.samples[self.cursor..end].to_vec()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
     }
 }
@@ -0,0 +1,142 @@
+use crate::analysis::NUM_BANDS;
+
+/// Rolling window length for the flux mean, ~0.7 seconds at 60fps.
+const HISTORY_LEN: usize = 43;
+/// Minimum frames between beats, so a single hit can't double-trigger.
+const REFRACTORY_FRAMES: u8 = 6;
+/// How fast the pulse envelope decays back towards 0 each frame.
+const PULSE_DECAY: f32 = 0.9;
+/// Beats only fire when flux exceeds this multiple of the rolling mean.
+const DEFAULT_SENSITIVITY: f32 = 1.5;
+
+/// Turns per-band magnitudes into beat events using spectral flux.
+///
+/// Tracks the previous frame's bands, sums the half-wave rectified rise in
+/// energy across all bands (low bands weighted higher to emphasize kick
+/// drums), and fires a beat when that flux spikes above its own rolling
+/// average and is rising faster than the frame before it.
+pub struct BeatDetector {
+    prev_bands: [f32; NUM_BANDS],
+    band_weights: [f32; NUM_BANDS],
+    flux_history: Vec<f32>,
+    sensitivity: f32,
+    refractory: u8,
+    pulse: f32,
+}
+
+impl BeatDetector {
+    pub fn new() -> Self {
+        Self::with_sensitivity(DEFAULT_SENSITIVITY)
+    }
+
+    pub fn with_sensitivity(sensitivity: f32) -> Self {
+        let mut band_weights = [1.0f32; NUM_BANDS];
+        for weight in &mut band_weights[..NUM_BANDS / 8] {
+            *weight = 2.0; // emphasize kick-drum range
+        }
+
+        Self {
+            prev_bands: [0.0; NUM_BANDS],
+            band_weights,
+            flux_history: Vec::with_capacity(HISTORY_LEN),
+            sensitivity,
+            refractory: 0,
+            pulse: 0.0,
+        }
+    }
+
+    /// Feed the latest smoothed band magnitudes in. Returns whether this
+    /// frame is a beat; use `pulse()` for the decaying visual envelope.
+    pub fn process(&mut self, bands: &[f32; NUM_BANDS]) -> bool {
+        let flux: f32 = bands
+            .iter()
+            .zip(self.prev_bands.iter())
+            .zip(self.band_weights.iter())
+            .map(|((&cur, &prev), &weight)| (cur - prev).max(0.0) * weight)
+            .sum();
+        self.prev_bands = *bands;
+
+        let mean = if self.flux_history.is_empty() {
+            0.0
+        } else {
+            self.flux_history.iter().sum::<f32>() / self.flux_history.len() as f32
+        };
+
+        // Realtime peak check: there's no future sample to compare against,
+        // so "local peak" is approximated as still rising over last frame.
+        let is_rising = self.flux_history.last().map_or(true, |&last| flux > last);
+
+        self.flux_history.push(flux);
+        if self.flux_history.len() > HISTORY_LEN {
+            self.flux_history.remove(0);
+        }
+
+        if self.refractory > 0 {
+            self.refractory -= 1;
+        }
+
+        let is_beat = self.refractory == 0 && flux > mean * self.sensitivity && is_rising;
+
+        if is_beat {
+            self.refractory = REFRACTORY_FRAMES;
+            self.pulse = 1.0;
+        } else {
+            self.pulse *= PULSE_DECAY;
+        }
+
+        is_beat
+    }
+
+    /// Decaying 0..1 envelope the visualizer can use to scale radius or
+    /// brightness on a hit.
+    pub fn pulse(&self) -> f32 {
+        self.pulse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence_never_beats() {
+        let mut detector = BeatDetector::new();
+        let silence = [0.0f32; NUM_BANDS];
+        for _ in 0..120 {
+            assert!(!detector.process(&silence));
+        }
+        assert_eq!(detector.pulse(), 0.0);
+    }
+
+    #[test]
+    fn test_sudden_spike_triggers_beat_and_pulse() {
+        let mut detector = BeatDetector::new();
+        let quiet = [0.1f32; NUM_BANDS];
+        for _ in 0..50 {
+            detector.process(&quiet);
+        }
+
+        let spike = [1.0f32; NUM_BANDS];
+        let beat = detector.process(&spike);
+        assert!(beat, "sudden energy rise should register as a beat");
+        assert!(detector.pulse() > 0.0);
+    }
+
+    #[test]
+    fn test_refractory_blocks_double_trigger() {
+        let mut detector = BeatDetector::new();
+        let quiet = [0.1f32; NUM_BANDS];
+        for _ in 0..50 {
+            detector.process(&quiet);
+        }
+
+        let spike = [1.0f32; NUM_BANDS];
+        assert!(detector.process(&spike));
+        // Same spike sustained shouldn't immediately re-trigger.
+        assert!(!detector.process(&spike));
+        // Dropping back and hitting again gives a fresh flux spike well above
+        // the mean, but it is still inside the refractory period.
+        detector.process(&quiet);
+        assert!(!detector.process(&spike));
+    }
+}
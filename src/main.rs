@@ -1,5 +1,6 @@
 mod analysis;
 mod audio;
+mod beat;
 mod colors;
 mod visualizer;
 
@@ -14,8 +15,9 @@ use crossterm::{
 };
 use ratatui::prelude::*;
 
-use analysis::Analyzer;
-use audio::AudioCapture;
+use analysis::{Analyzer, AnalyzerConfig};
+use audio::{AudioCapture, AudioSource, FileSource};
+use beat::BeatDetector;
 use visualizer::RadialVisualizer;
 
 const TARGET_FPS: u64 = 60;
@@ -35,21 +37,40 @@ fn main() -> Result<()> {
         original_hook(panic);
     }));
 
-    // Initialize audio and analyzer
-    let capture = match AudioCapture::new() {
-        Ok(c) => c,
-        Err(e) => {
-            cleanup_terminal()?;
-            eprintln!("Failed to initialize audio capture: {}", e);
-            eprintln!("Make sure audio is playing on your system.");
-            return Ok(());
-        }
+    // Pick an audio source: a file path on the command line plays and
+    // visualizes that track, otherwise fall back to system loopback capture.
+    let file_arg = std::env::args().nth(1);
+    let mut source: Box<dyn AudioSource> = match file_arg {
+        Some(path) => match FileSource::new(std::path::Path::new(&path)) {
+            Ok(s) => Box::new(s),
+            Err(e) => {
+                cleanup_terminal()?;
+                eprintln!("Failed to open audio file '{}': {}", path, e);
+                return Ok(());
+            }
+        },
+        None => match AudioCapture::new() {
+            Ok(c) => Box::new(c),
+            Err(e) => {
+                cleanup_terminal()?;
+                eprintln!("Failed to initialize audio capture: {}", e);
+                eprintln!("Make sure audio is playing on your system.");
+                return Ok(());
+            }
+        },
     };
 
-    let mut analyzer = Analyzer::new();
+    // Read the source's real sample rate rather than assuming 44.1kHz, so
+    // band mapping stays correct on 48kHz devices and files alike.
+    let analyzer_config = AnalyzerConfig {
+        sample_rate: source.sample_rate(),
+        ..AnalyzerConfig::default()
+    };
+    let mut analyzer = Analyzer::new(analyzer_config);
+    let mut beat_detector = BeatDetector::new();
 
     // Main loop
-    let result = run_loop(&mut terminal, &capture, &mut analyzer);
+    let result = run_loop(&mut terminal, source.as_mut(), &mut analyzer, &mut beat_detector);
 
     // Cleanup
     cleanup_terminal()?;
@@ -59,8 +80,9 @@ fn main() -> Result<()> {
 
 fn run_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    capture: &AudioCapture,
+    source: &mut dyn AudioSource,
     analyzer: &mut Analyzer,
+    beat_detector: &mut BeatDetector,
 ) -> Result<()> {
     loop {
         let frame_start = Instant::now();
@@ -79,8 +101,10 @@ fn run_loop(
         }
 
         // Get audio and analyze
-        let samples = capture.get_samples();
+        let samples = source.get_samples();
         let bands = analyzer.process(&samples);
+        beat_detector.process(&bands);
+        let pulse = beat_detector.pulse();
 
         // Render
         terminal.draw(|frame| {
@@ -95,7 +119,7 @@ fn run_loop(
                 return;
             }
 
-            let viz = RadialVisualizer::new(bands);
+            let viz = RadialVisualizer::new(bands, pulse);
             frame.render_widget(viz, area);
         })?;
 
@@ -9,11 +9,12 @@ use ratatui::{
 
 pub struct RadialVisualizer {
     bands: [f32; NUM_BANDS],
+    pulse: f32,
 }
 
 impl RadialVisualizer {
-    pub fn new(bands: [f32; NUM_BANDS]) -> Self {
-        Self { bands }
+    pub fn new(bands: [f32; NUM_BANDS], pulse: f32) -> Self {
+        Self { bands, pulse }
     }
 }
 
@@ -27,15 +28,21 @@ impl Widget for RadialVisualizer {
         let max_radius_x = (area.width / 2) as f32;
         let max_radius_y = (area.height / 2) as f32 * 2.0; // Adjust for aspect ratio
 
-        let max_radius = max_radius_x.min(max_radius_y) * 0.9;
+        // Flash the ring outward on a beat, decaying back to the resting size.
+        let max_radius = max_radius_x.min(max_radius_y) * 0.9 * (1.0 + self.pulse * 0.25);
 
         // Draw each band as a spoke
         for band in 0..NUM_BANDS {
             let angle = (band as f32 / NUM_BANDS as f32) * 2.0 * std::f32::consts::PI;
-            let intensity = self.bands[band];
+            // Scaling modes like DivideByNSqrt aren't normalized to 0..1, so
+            // clamp here rather than let an over-1.0 band blow spoke length
+            // past max_radius.
+            let intensity = self.bands[band].clamp(0.0, 1.0);
             let length = max_radius * (0.2 + intensity * 0.8); // Min 20% length
 
-            let (r, g, b) = band_to_color(band, intensity);
+            // Brighten spokes on a beat hit as well as expanding the ring.
+            let lit_intensity = (intensity + self.pulse * 0.3).min(1.0);
+            let (r, g, b) = band_to_color(band, lit_intensity);
             let color = Color::Rgb(r, g, b);
 
             // Draw spoke from center outward
@@ -83,7 +90,7 @@ mod tests {
     #[test]
     fn test_visualizer_creation() {
         let bands = [0.5; NUM_BANDS];
-        let viz = RadialVisualizer::new(bands);
+        let viz = RadialVisualizer::new(bands, 0.0);
         assert_eq!(viz.bands[0], 0.5);
     }
 }